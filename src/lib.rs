@@ -5,12 +5,23 @@
 
 #[cfg(test)]
 extern crate mockito;
+extern crate async_trait;
+extern crate futures;
+extern crate md4;
 extern crate regex;
 extern crate reqwest;
 extern crate sha1;
 
+mod offline;
+
+pub use offline::{PasswordSource, PwnedDatabase};
+
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use md4::Md4;
 use regex::Regex;
 use sha1::{Digest, Sha1};
+use std::collections::HashMap;
 use std::error;
 use std::fmt;
 
@@ -46,6 +57,12 @@ pub enum Error {
     Parse(std::num::ParseIntError),
     Reqwest(reqwest::Error),
     Regex(regex::Error),
+    Io(std::io::Error),
+    /// A lookup shared by several passwords (e.g. a [`Client::check_many`]
+    /// prefix bucket) failed; wraps the underlying error in an `Arc` since
+    /// it can't be cloned to every password it affected, while keeping the
+    /// original error type and its `cause()` chain intact.
+    Batch(std::sync::Arc<Error>),
 }
 
 impl fmt::Display for Error {
@@ -55,6 +72,8 @@ impl fmt::Display for Error {
             Error::Parse(ref err) => write!(f, "Parse error: {}", err),
             Error::Reqwest(ref err) => write!(f, "Reqwest error: {}", err),
             Error::Regex(ref err) => write!(f, "Regex error: {}", err),
+            Error::Io(ref err) => write!(f, "IO error: {}", err),
+            Error::Batch(ref err) => write!(f, "Batch error: {}", err),
         }
     }
 }
@@ -66,6 +85,8 @@ impl error::Error for Error {
             Error::Parse(ref err) => Some(err),
             Error::Reqwest(ref err) => Some(err),
             Error::Regex(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            Error::Batch(ref err) => Some(err.as_ref()),
         }
     }
 }
@@ -94,9 +115,157 @@ impl From<regex::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+/// Checks how many times a password appears in the HaveIBeenPwned database.
+///
+/// Returns `Ok(0)` if the password is not known, `Ok(n)` if it was found `n`
+/// times, and `Err` only for genuine I/O/parse failures.
+///
+/// This is a convenience wrapper around [`Client::pwned_count`] using a
+/// default, freshly-built [`Client`]. Checking many passwords should go
+/// through a shared `Client` instead, to reuse its connection pool.
+///
+/// # Arguments
+///
+/// * `password` - The password to check
+///
+/// # Example
+///
+/// ```no_run
+/// use hibp::pwned_count;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let count = pwned_count("test").await;
+///     assert!(count.unwrap() > 0);
+/// }
+/// ```
+pub async fn pwned_count(password: &str) -> Result<u64, Error> {
+    Client::new().pwned_count(password).await
+}
+
+/// A hash algorithm supported by the Pwned Passwords range API.
+///
+/// `Sha1` is used for regular password auditing; `Ntlm` matches the digest
+/// Windows stores for credentials, for auditing Active Directory exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Ntlm,
+}
+
+impl Algorithm {
+    fn hash(self, password: &str) -> String {
+        match self {
+            Algorithm::Sha1 => hash(password),
+            Algorithm::Ntlm => ntlm_hash(password),
+        }
+    }
+
+    fn query_suffix(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "",
+            Algorithm::Ntlm => "?mode=ntlm",
+        }
+    }
+}
+
+/// Computes the full, uppercase, 32-char hex NTLM (MD4 over UTF-16LE) digest
+/// of `password`.
+fn ntlm_hash(password: &str) -> String {
+    let utf16le: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let digest = Md4::new().chain(utf16le).result();
+    format!("{:X}", digest)
+}
+
+/// Checks how many times a password appears in the HaveIBeenPwned database,
+/// requesting [padded](https://haveibeenpwned.com/API/v3#PwnedPasswordsPadding)
+/// responses from the API so that an eavesdropper cannot infer which suffix
+/// bucket was requested from the response size.
+///
+/// Bogus entries the API pads the response with always carry a usage count
+/// of `0` and are discarded before matching, so they cannot shadow a real
+/// result.
+///
+/// # Arguments
+///
+/// * `password` - The password to check
+///
+/// # Example
+///
+/// ```no_run
+/// use hibp::pwned_count_with_padding;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let count = pwned_count_with_padding("test", true).await;
+///     assert!(count.unwrap() > 0);
+/// }
+/// ```
+pub async fn pwned_count_with_padding(password: &str, padded: bool) -> Result<u64, Error> {
+    Client::new().padded(padded).pwned_count(password).await
+}
+
+/// Checks how many times a password appears in the HaveIBeenPwned database,
+/// with full control over padding and the hash [`Algorithm`] used.
+///
+/// # Arguments
+///
+/// * `password` - The password to check
+/// * `padded` - Whether to request a padded response
+/// * `algorithm` - The hash algorithm to match the password against
+///
+/// # Example
+///
+/// ```no_run
+/// use hibp::{pwned_count_with_options, Algorithm};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let count = pwned_count_with_options("test", false, Algorithm::Ntlm).await;
+///     assert!(count.unwrap() > 0);
+/// }
+/// ```
+pub async fn pwned_count_with_options(
+    password: &str,
+    padded: bool,
+    algorithm: Algorithm,
+) -> Result<u64, Error> {
+    Client::new()
+        .padded(padded)
+        .algorithm(algorithm)
+        .pwned_count(password)
+        .await
+}
+
+/// Parses a range-lookup response body, returning the usage count for
+/// `suffix` while discarding zero-count padding decoys.
+fn parse_range_response(response: &str, suffix: &str) -> Result<u64, Error> {
+    let reg = Regex::new(r"(?m)^([0-9A-F]+):(\d+)")?;
+
+    for c in reg.captures_iter(response) {
+        let uses: u64 = c.get(2).map_or("", |m| m.as_str()).parse()?;
+        if uses == 0 {
+            // Padding decoys always carry a zero count; skip them so they
+            // can never shadow a genuine match.
+            continue;
+        }
+        if c.get(1).map_or("", |m| m.as_str()) == suffix {
+            return Ok(uses);
+        }
+    }
+
+    Ok(0)
+}
+
 /// Checks if a password have been pwned.
-/// 
-/// Returns a Ok if the password is not known and an Error otherwise.  
+///
+/// Returns a Ok if the password is not known and an Error otherwise.
 /// The error will contain the number of time the password is present
 /// in the HaveIBeenPwned database
 ///
@@ -108,7 +277,7 @@ impl From<regex::Error> for Error {
 ///
 /// ```
 /// use hibp::check;
-/// 
+///
 /// #[tokio::main]
 /// async fn main() {
 ///     let checked = check("test".to_string()).await;
@@ -116,29 +285,230 @@ impl From<regex::Error> for Error {
 /// }
 /// ```
 pub async fn check(password: String) -> Result<(), Error> {
-    let hash = hash(password);
+    Client::new().check(password).await
+}
 
-    #[cfg(not(test))]
-    let url = format!("https://api.pwnedpasswords.com/range/{}", hash.0);
-    #[cfg(test)]
-    let url = format!("{}/range/{}", mockito::server_url(), hash.0);
+/// Computes the full, uppercase, 40-char hex SHA-1 digest of `password`.
+pub(crate) fn hash<T: AsRef<[u8]>>(password: T) -> String {
+    let hash = Sha1::new().chain(password).result();
+    format!("{:X}", hash)
+}
 
-    let response = reqwest::get(&url).await?.text().await?;
-    let reg = Regex::new(&format!(r"{}:(\d+)", hash.1))?;
+/// A client for the online HaveIBeenPwned Pwned Passwords API.
+///
+/// Holds a pooled [`reqwest::Client`] so that checking many passwords does
+/// not pay the cost of a fresh TLS connection per lookup, and carries the
+/// base URL, user agent and lookup options used on every request. Lookup
+/// methods take `&self`, so a `Client` can be shared across concurrent
+/// lookups; the builder methods take and return the `Client` by value.
+///
+/// # Example
+///
+/// ```no_run
+/// use hibp::Client;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = Client::new();
+///     let count = client.pwned_count("test").await;
+///     assert!(count.unwrap() > 0);
+/// }
+/// ```
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    user_agent: String,
+    padded: bool,
+    algorithm: Algorithm,
+}
 
-    match reg.captures(&response) {
-        Some(c) => {
-            let uses: i32 = c.get(1).map_or("", |m| m.as_str()).parse()?;
-            Err(Error::Pwned(<PwnedError>::new(uses)))
+impl Client {
+    /// Creates a client pointed at the official HaveIBeenPwned API.
+    pub fn new() -> Client {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: "https://api.pwnedpasswords.com".to_string(),
+            user_agent: format!("hibp-rs/{}", env!("CARGO_PKG_VERSION")),
+            padded: false,
+            algorithm: Algorithm::Sha1,
         }
-        None => Ok(()),
+    }
+
+    /// Points the client at a different base URL, e.g. a mirror or a test
+    /// server, instead of the official API.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Client {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Client {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Requests padded responses from the API, see
+    /// [`pwned_count_with_padding`].
+    pub fn padded(mut self, padded: bool) -> Client {
+        self.padded = padded;
+        self
+    }
+
+    /// Selects the hash [`Algorithm`] matched against, see
+    /// [`pwned_count_with_options`].
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Client {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Checks how many times a password appears in the HaveIBeenPwned
+    /// database, using this client's connection pool and options.
+    pub async fn pwned_count(&self, password: &str) -> Result<u64, Error> {
+        let full = self.algorithm.hash(password);
+        let (prefix, suffix) = full.split_at(5);
+        let response = self.fetch_range(prefix).await?;
+        parse_range_response(&response, suffix)
+    }
+
+    /// Checks if a password have been pwned, using this client's connection
+    /// pool and options. See [`check`].
+    pub async fn check(&self, password: String) -> Result<(), Error> {
+        match self.pwned_count(&password).await? {
+            0 => Ok(()),
+            uses => Err(Error::Pwned(<PwnedError>::new(uses as i32))),
+        }
+    }
+
+    /// Checks many passwords at once, coalescing passwords that share a
+    /// 5-char hash prefix into a single range request and running at most
+    /// `concurrency` requests in flight.
+    ///
+    /// Returns one `(password, result)` pair per input, in no particular
+    /// order. A request failure is reported to every password in the prefix
+    /// bucket it belonged to, as [`Error::Batch`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use hibp::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let client = Client::new();
+    ///     let passwords = vec!["test".to_string(), "hunter2".to_string()];
+    ///     let results = client.check_many(passwords, 4).await;
+    ///     assert_eq!(results.len(), 2);
+    /// }
+    /// ```
+    pub async fn check_many<I>(
+        &self,
+        passwords: I,
+        concurrency: usize,
+    ) -> Vec<(String, Result<u64, Error>)>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut buckets: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for password in passwords {
+            let full = self.algorithm.hash(&password);
+            let (prefix, suffix) = full.split_at(5);
+            buckets
+                .entry(prefix.to_string())
+                .or_insert_with(Vec::new)
+                .push((password, suffix.to_string()));
+        }
+
+        stream::iter(buckets.into_iter())
+            .map(|(prefix, members)| async move {
+                self.check_prefix_bucket(&prefix, members).await
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Fetches and checks every `(password, suffix)` pair that shares
+    /// `prefix`, with a single range request.
+    async fn check_prefix_bucket(
+        &self,
+        prefix: &str,
+        members: Vec<(String, String)>,
+    ) -> Vec<(String, Result<u64, Error>)> {
+        match self.fetch_range(prefix).await {
+            Ok(response) => members
+                .into_iter()
+                .map(|(password, suffix)| {
+                    let count = parse_range_response(&response, &suffix);
+                    (password, count)
+                })
+                .collect(),
+            Err(err) => {
+                let err = std::sync::Arc::new(err);
+                members
+                    .into_iter()
+                    .map(|(password, _)| (password, Err(Error::Batch(err.clone()))))
+                    .collect()
+            }
+        }
+    }
+
+    /// Fetches the raw range-lookup response body for a 5-char hash prefix.
+    async fn fetch_range(&self, prefix: &str) -> Result<String, Error> {
+        let url = format!(
+            "{}/range/{}{}",
+            self.base_url,
+            prefix,
+            self.algorithm.query_suffix()
+        );
+
+        let mut request = self.http.get(&url).header("User-Agent", &self.user_agent);
+        if self.padded {
+            request = request.header("Add-Padding", "true");
+        }
+
+        Ok(request.send().await?.error_for_status()?.text().await?)
     }
 }
 
-fn hash(password: String) -> (String, String) {
-    let hash = Sha1::new().chain(password).result();
-    let hex = format!("{:X}", hash);
-    (hex[0..5].to_string(), hex.clone()[5..].to_string())
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+/// A [`PasswordSource`] backed by the online HaveIBeenPwned API.
+///
+/// # Example
+///
+/// ```no_run
+/// use hibp::{Client, OnlineSource, PasswordSource};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let source = OnlineSource::new(Client::new());
+///     let count = source.pwned_count("test").await;
+///     assert!(count.unwrap() > 0);
+/// }
+/// ```
+pub struct OnlineSource {
+    client: Client,
+}
+
+impl OnlineSource {
+    /// Wraps a [`Client`] as a [`PasswordSource`].
+    pub fn new(client: Client) -> OnlineSource {
+        OnlineSource { client }
+    }
+}
+
+#[async_trait]
+impl PasswordSource for OnlineSource {
+    async fn pwned_count(&self, password: &str) -> Result<u64, Error> {
+        self.client.pwned_count(password).await
+    }
 }
 
 #[cfg(test)]
@@ -149,13 +519,153 @@ mod tests {
 
         let hashed = hash("test".to_string());
 
-        assert_eq!(hashed.0.chars().count(), 5);
-        assert_eq!(hashed.1.chars().count(), 35);
+        assert_eq!(hashed.chars().count(), 40);
+        assert_eq!(hashed, "A94A8FE5CCB19BA61C4C0873D391E987982FBBD3");
+    }
+
+    #[tokio::test]
+    async fn it_counts_with_zero() {
+        use super::Client;
+        use mockito::mock;
+
+        // A94A8 is 0..5 of 'test' SHA1
+        // FE5CCB19BA61C4C0873D391E987982FBBD3 is 5.. of 'test' SHA1
+
+        let _m = mock("GET", "/range/A94A8")
+            .with_status(200)
+            .with_body(
+                "
+FD8D510BFF2210462F26307C2143E990E6E:2
+FDFAEE848356AD27F8FB494E5C1B11956C2:2
+FF36DC7D3284A39991ADA90CAF20D1E3C0D:1
+FFF983A91443AE72BD98E59ADAB93B31974:2
+",
+            )
+            .create();
+
+        let client = Client::new().base_url(mockito::server_url());
+        let count = client.pwned_count("test").await;
+        assert_eq!(count.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_counts_with_matches() {
+        use super::Client;
+        use mockito::mock;
+
+        // A94A8 is 0..5 of 'test' SHA1
+        // FE5CCB19BA61C4C0873D391E987982FBBD3 is 5.. of 'test' SHA1
+
+        let _m = mock("GET", "/range/A94A8")
+            .with_status(200)
+            .with_body(
+                "
+FD8D510BFF2210462F26307C2143E990E6E:2
+FDFAEE848356AD27F8FB494E5C1B11956C2:2
+FE5CCB19BA61C4C0873D391E987982FBBD3:42
+FF36DC7D3284A39991ADA90CAF20D1E3C0D:1
+FFF983A91443AE72BD98E59ADAB93B31974:2
+",
+            )
+            .create();
+
+        let client = Client::new().base_url(mockito::server_url());
+        let count = client.pwned_count("test").await;
+        assert_eq!(count.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn it_counts_with_padding_ignoring_decoys() {
+        use super::Client;
+        use mockito::mock;
+
+        // A94A8 is 0..5 of 'test' SHA1
+        // FE5CCB19BA61C4C0873D391E987982FBBD3 is 5.. of 'test' SHA1
+
+        let _m = mock("GET", "/range/A94A8")
+            .match_header("Add-Padding", "true")
+            .with_status(200)
+            .with_body(
+                "
+FD8D510BFF2210462F26307C2143E990E6E:0
+FDFAEE848356AD27F8FB494E5C1B11956C2:0
+FE5CCB19BA61C4C0873D391E987982FBBD3:42
+FF36DC7D3284A39991ADA90CAF20D1E3C0D:0
+FFF983A91443AE72BD98E59ADAB93B31974:0
+",
+            )
+            .create();
+
+        let client = Client::new().base_url(mockito::server_url()).padded(true);
+        let count = client.pwned_count("test").await;
+        assert_eq!(count.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn it_counts_with_padding_and_no_match() {
+        use super::Client;
+        use mockito::mock;
+
+        // A94A8 is 0..5 of 'test' SHA1
+        // FE5CCB19BA61C4C0873D391E987982FBBD3 is 5.. of 'test' SHA1,
+        // but the server only returns zero-count padding decoys here.
+
+        let _m = mock("GET", "/range/A94A8")
+            .match_header("Add-Padding", "true")
+            .with_status(200)
+            .with_body(
+                "
+FD8D510BFF2210462F26307C2143E990E6E:0
+FE5CCB19BA61C4C0873D391E987982FBBD3:0
+FF36DC7D3284A39991ADA90CAF20D1E3C0D:0
+",
+            )
+            .create();
+
+        let client = Client::new().base_url(mockito::server_url()).padded(true);
+        let count = client.pwned_count("test").await;
+        assert_eq!(count.unwrap(), 0);
+    }
+
+    #[test]
+    fn it_hashes_ntlm() {
+        use super::ntlm_hash;
+
+        // NTLM hash of "test" is known to be 0CB6948805F797BF2A82807973B89537
+        let hashed = ntlm_hash("test");
+
+        assert_eq!(hashed.chars().count(), 32);
+        assert_eq!(hashed, "0CB6948805F797BF2A82807973B89537");
+    }
+
+    #[tokio::test]
+    async fn it_counts_with_ntlm_mode() {
+        use super::{Algorithm, Client};
+        use mockito::mock;
+
+        // 0CB69 is 0..5 of 'test' NTLM
+        // 48805F797BF2A82807973B89537 is 5.. of 'test' NTLM
+
+        let _m = mock("GET", "/range/0CB69?mode=ntlm")
+            .with_status(200)
+            .with_body(
+                "
+48805F797BF2A82807973B89537:7
+FFFFFFFFFFFFFFFFFFFFFFFFFFF:1
+",
+            )
+            .create();
+
+        let client = Client::new()
+            .base_url(mockito::server_url())
+            .algorithm(Algorithm::Ntlm);
+        let count = client.pwned_count("test").await;
+        assert_eq!(count.unwrap(), 7);
     }
 
     #[tokio::test]
     async fn it_checks_with_ok() {
-        use super::check;
+        use super::Client;
         use mockito::mock;
 
         // A94A8 is 0..5 of 'test' SHA1
@@ -173,13 +683,14 @@ FFF983A91443AE72BD98E59ADAB93B31974:2
             )
             .create();
 
-        let checked = check("test".to_string()).await;
+        let client = Client::new().base_url(mockito::server_url());
+        let checked = client.check("test".to_string()).await;
         assert!(checked.is_ok());
     }
 
     #[tokio::test]
     async fn it_checks_with_err() {
-        use super::check;
+        use super::Client;
         use super::Error;
         use mockito::mock;
 
@@ -199,11 +710,78 @@ FFF983A91443AE72BD98E59ADAB93B31974:2
             )
             .create();
 
-        let err: Error = check("test".to_string()).await.unwrap_err();
+        let client = Client::new().base_url(mockito::server_url());
+        let err: Error = client.check("test".to_string()).await.unwrap_err();
 
         match err {
             Error::Pwned(ref err) => assert_eq!(err.uses, 42),
             _ => panic!("Wrong error type"),
         };
     }
+
+    #[tokio::test]
+    async fn it_checks_many_across_prefix_buckets() {
+        use super::Client;
+        use mockito::mock;
+        use std::collections::HashMap;
+
+        // A94A8 is 0..5 of 'test' SHA1
+        // FE5CCB19BA61C4C0873D391E987982FBBD3 is 5.. of 'test' SHA1
+        let _test_mock = mock("GET", "/range/A94A8")
+            .with_status(200)
+            .with_body("FE5CCB19BA61C4C0873D391E987982FBBD3:42\n")
+            .create();
+
+        // 5BAA6 is 0..5 of 'password' SHA1
+        // 1E4C9B93F3F0682250B6CF8331B7EE68FD8 is 5.. of 'password' SHA1
+        let _password_mock = mock("GET", "/range/5BAA6")
+            .with_status(200)
+            .with_body("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:1\n")
+            .create();
+
+        let client = Client::new().base_url(mockito::server_url());
+        let passwords = vec!["test".to_string(), "password".to_string()];
+        let results: HashMap<String, u64> = client
+            .check_many(passwords, 4)
+            .await
+            .into_iter()
+            .map(|(password, count)| (password, count.unwrap()))
+            .collect();
+
+        assert_eq!(results["test"], 42);
+        assert_eq!(results["password"], 0);
+    }
+
+    #[tokio::test]
+    async fn it_checks_many_reports_batch_error_for_whole_bucket() {
+        use super::{Client, Error};
+        use mockito::mock;
+
+        // A94A8 is 0..5 of 'test' SHA1, 5BAA6 is 0..5 of 'password' SHA1;
+        // they fall into different prefix buckets, and each bucket fails
+        // independently of the others.
+        let _test_mock = mock("GET", "/range/A94A8")
+            .with_status(500)
+            .with_body("garbage")
+            .create();
+        let _password_mock = mock("GET", "/range/5BAA6")
+            .with_status(200)
+            .with_body("1E4C9B93F3F0682250B6CF8331B7EE68FD8:1\n")
+            .create();
+
+        let client = Client::new().base_url(mockito::server_url());
+        let passwords = vec!["test".to_string(), "password".to_string()];
+        let results = client.check_many(passwords, 4).await;
+
+        for (password, result) in results {
+            if password == "test" {
+                match result {
+                    Err(Error::Batch(ref err)) => assert!(matches!(**err, Error::Reqwest(_))),
+                    _ => panic!("expected a batch error for the failing prefix bucket"),
+                }
+            } else {
+                assert_eq!(result.unwrap(), 1);
+            }
+        }
+    }
 }
@@ -0,0 +1,179 @@
+//! Offline, air-gapped lookups against a downloaded Pwned Passwords hash dump.
+//!
+//! The official dump ships as a plain text file of `hash:count` lines sorted
+//! by hash, one per known password
+//! (https://haveibeenpwned.com/Passwords), and runs to hundreds of millions
+//! of rows. [`PwnedDatabase`] indexes it into one contiguous buffer of
+//! fixed-size records (no per-row heap allocation) and answers lookups with
+//! a binary search, so large batches of passwords can be checked without a
+//! single network round-trip.
+
+use crate::{hash, Error};
+use async_trait::async_trait;
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// A source of pwnage counts, implemented by both the online API and
+/// [`PwnedDatabase`] so callers can pick a backend without changing call
+/// sites.
+#[async_trait]
+pub trait PasswordSource {
+    /// Returns how many times `password` appears in the backing database,
+    /// or `0` if it is not known.
+    async fn pwned_count(&self, password: &str) -> Result<u64, Error>;
+}
+
+/// Bytes per indexed record: a 20-byte SHA-1 digest followed by a 4-byte
+/// native-endian usage count.
+const RECORD_SIZE: usize = 20 + 4;
+
+/// An in-memory index over a downloaded Pwned Passwords SHA-1 hash dump.
+///
+/// The dump is expected to already be sorted by hash, as the official
+/// download is; `PwnedDatabase::open` packs it into one `Vec<u8>` of
+/// fixed-size `(hash, count)` records (rather than a `Vec` of individually
+/// heap-allocated entries) so indexing the real ~900M-row dump stays within
+/// a plausible memory budget, and every subsequent lookup is a binary
+/// search instead of a linear scan.
+pub struct PwnedDatabase {
+    entries: Vec<u8>,
+}
+
+impl PwnedDatabase {
+    /// Loads and indexes a `hash:count` dump from `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PwnedDatabase, Error> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        // A `HASH:COUNT\n` line is ~43 bytes; reserving up front avoids
+        // repeated reallocation while walking the dump.
+        let estimated_entries = (file_len / 43) as usize;
+        let mut entries = Vec::with_capacity(estimated_entries * RECORD_SIZE);
+
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+                line.pop();
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let colon = match line.iter().position(|&b| b == b':') {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let digest = match parse_hex_hash(&line[..colon]) {
+                Some(digest) => digest,
+                None => continue,
+            };
+            let count: u32 = match std::str::from_utf8(&line[colon + 1..])
+                .ok()
+                .and_then(|s| s.parse().ok())
+            {
+                Some(count) => count,
+                None => continue,
+            };
+
+            entries.extend_from_slice(&digest);
+            entries.extend_from_slice(&count.to_ne_bytes());
+        }
+
+        Ok(PwnedDatabase { entries })
+    }
+
+    /// Looks up a full 40-char hex SHA-1 hash, returning its usage count or
+    /// `0` if it is absent from the database.
+    fn lookup(&self, full_hash: &str) -> u64 {
+        let needle = match parse_hex_hash(full_hash.as_bytes()) {
+            Some(digest) => digest,
+            None => return 0,
+        };
+
+        let record_count = self.entries.len() / RECORD_SIZE;
+        let mut low = 0;
+        let mut high = record_count;
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let offset = mid * RECORD_SIZE;
+            let candidate = &self.entries[offset..offset + 20];
+
+            match candidate.cmp(&needle[..]) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => {
+                    let count = &self.entries[offset + 20..offset + RECORD_SIZE];
+                    return u32::from_ne_bytes(count.try_into().unwrap()) as u64;
+                }
+            }
+        }
+
+        0
+    }
+}
+
+/// Parses a 40-char uppercase hex SHA-1 digest into raw bytes.
+fn parse_hex_hash(hex: &[u8]) -> Option<[u8; 20]> {
+    if hex.len() != 40 {
+        return None;
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        let hi = (hex[i * 2] as char).to_digit(16)?;
+        let lo = (hex[i * 2 + 1] as char).to_digit(16)?;
+        *byte = ((hi << 4) | lo) as u8;
+    }
+    Some(digest)
+}
+
+#[async_trait]
+impl PasswordSource for PwnedDatabase {
+    async fn pwned_count(&self, password: &str) -> Result<u64, Error> {
+        Ok(self.lookup(&hash(password)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PasswordSource, PwnedDatabase};
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn it_finds_a_known_hash() {
+        let mut file = tempfile_with_contents(
+            "A94A8FE5CCB19BA61C4C0873D391E987982FBBD3:42\nFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:1\n",
+        );
+
+        let db = PwnedDatabase::open(file.path()).unwrap();
+        let count = db.pwned_count("test").await.unwrap();
+
+        assert_eq!(count, 42);
+        file.flush().unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_returns_zero_for_an_unknown_hash() {
+        let file = tempfile_with_contents("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:1\n");
+
+        let db = PwnedDatabase::open(file.path()).unwrap();
+        let count = db.pwned_count("test").await.unwrap();
+
+        assert_eq!(count, 0);
+    }
+
+    fn tempfile_with_contents(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+}